@@ -1,8 +1,10 @@
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 use serde::Serialize;
+use tokio::sync::Semaphore;
 use thiserror::Error;
 pub use reqwest::{Request, RequestBuilder, StatusCode, Method, header::{self, HeaderMap}};
 pub use bytes::Bytes;
+use bytes::BytesMut;
 
 /// The User Agent string
 pub static USER_AGENT: &str = concat!("generic-api-client/", env!("CARGO_PKG_VERSION"));
@@ -14,6 +16,7 @@ pub static USER_AGENT: &str = concat!("generic-api-client/", env!("CARGO_PKG_VER
 #[derive(Debug, Clone)]
 pub struct Client {
     client: reqwest::Client,
+    semaphore: Option<Arc<Semaphore>>,
 }
 
 impl Client {
@@ -23,6 +26,21 @@ impl Client {
         Self::default()
     }
 
+    /// Constructs a new `Client` using the given [ClientConfig].
+    pub fn with_config(config: ClientConfig) -> Self {
+        let client = reqwest::ClientBuilder::new()
+            .user_agent(USER_AGENT)
+            .tcp_keepalive(config.tcp_keepalive)
+            .pool_idle_timeout(config.pool_idle_timeout)
+            .pool_max_idle_per_host(config.pool_max_idle_per_host)
+            .build()
+            .unwrap(); // user agent should be valid
+        Self {
+            client,
+            semaphore: config.max_concurrent_requests.map(|permits| Arc::new(Semaphore::new(permits.clamp(1, Semaphore::MAX_PERMITS)))),
+        }
+    }
+
     /// Makes an HTTP request with the given [RequestHandler] and returns the response.
     ///
     /// It is recommended to use methods like [get()][Self::get()] because this method takes many type parameters and parameters.
@@ -39,21 +57,46 @@ impl Client {
         let config = handler.request_config();
         config.verify();
         let url = config.url_prefix + url;
+        #[cfg(feature = "tracing")]
+        let request_span = tracing::debug_span!("request", %method, %url);
+        // held until this request completes
+        let _permit = match &self.semaphore {
+            Some(semaphore) => Some(semaphore.acquire().await.unwrap()), // the semaphore is never closed
+            None => None,
+        };
         let mut count = 1;
         loop {
             // create RequestBuilder
             let mut request_builder = self.client.request(method.clone(), url.clone())
                 .timeout(config.timeout);
+            if let Some(user_agent) = &config.user_agent {
+                request_builder = request_builder.header(header::USER_AGENT, user_agent);
+            }
             if let Some(query) = query {
                 request_builder = request_builder.query(query);
             }
-            let request = handler.build_request(request_builder, &body, count).map_err(RequestError::BuildRequestError)?;
+            let request = {
+                #[cfg(feature = "tracing")]
+                let _span = tracing::debug_span!(parent: &request_span, "build_request", attempt = count).entered();
+                handler.build_request(request_builder, &body, count).map_err(RequestError::BuildRequestError)?
+            };
+            #[cfg(feature = "tracing")]
+            let sent_at = std::time::Instant::now();
             // send the request
             match self.client.execute(request).await {
                 Ok(mut response) => {
                     let status = response.status();
                     let headers = std::mem::take(response.headers_mut());
-                    let body = response.bytes().await.map_err(RequestError::ReceiveResponse)?;
+                    let body = match config.max_response_size {
+                        Some(limit) => Self::bytes_with_limit(response, limit).await?,
+                        None => response.bytes().await.map_err(RequestError::ReceiveResponse)?,
+                    };
+                    #[cfg(feature = "tracing")]
+                    let _span = tracing::debug_span!(
+                        parent: &request_span, "handle_response",
+                        status = status.as_u16(),
+                        latency_ms = sent_at.elapsed().as_millis() as u64,
+                    ).entered();
                     return handler.handle_response(status, headers, body).map_err(RequestError::ResponseHandleError);
                 },
                 Err(error) => {
@@ -70,6 +113,21 @@ impl Client {
         }
     }
 
+    async fn bytes_with_limit<E, R>(mut response: reqwest::Response, limit: usize) -> Result<Bytes, RequestError<E, R>> {
+        if response.content_length().is_some_and(|length| length > limit as u64) {
+            return Err(RequestError::ResponseTooLarge);
+        }
+        // Content-Length could be missing or wrong, so we count the bytes received as well
+        let mut body = BytesMut::new();
+        while let Some(chunk) = response.chunk().await.map_err(RequestError::ReceiveResponse)? {
+            if body.len() + chunk.len() > limit {
+                return Err(RequestError::ResponseTooLarge);
+            }
+            body.extend_from_slice(&chunk);
+        }
+        Ok(body.freeze())
+    }
+
     /// Makes an GET request with the given [RequestHandler].
     ///
     /// This method just calls [request()][Self::request()]. It requires less typing for type parameters and parameters.
@@ -185,12 +243,51 @@ impl Client {
 
 impl Default for Client {
     fn default() -> Self {
-        let client = reqwest::ClientBuilder::new()
-            .user_agent(USER_AGENT)
-            .build()
-            .unwrap(); // user agent should be valid
+        Self::with_config(ClientConfig::default())
+    }
+}
+
+/// Configuration of the connections made by a [Client].
+///
+/// Unlike [RequestConfig], this is applied when the [Client] is constructed using [Client::with_config()],
+/// and is shared by all requests sent using the [Client].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct ClientConfig {
+    /// The interval of TCP keep-alive probes. [Default]s to `None`, which disables TCP keep-alive.
+    ///
+    /// See also: [reqwest::ClientBuilder::tcp_keepalive()].
+    pub tcp_keepalive: Option<Duration>,
+    /// Idle connections in the pool are closed after this amount of time.
+    /// `None` means they are never closed. [Default]s to 90s.
+    ///
+    /// See also: [reqwest::ClientBuilder::pool_idle_timeout()].
+    pub pool_idle_timeout: Option<Duration>,
+    /// The maximum number of idle connections kept in the pool for each host. [Default]s to [usize::MAX].
+    ///
+    /// See also: [reqwest::ClientBuilder::pool_max_idle_per_host()].
+    pub pool_max_idle_per_host: usize,
+    /// The maximum number of requests that can be in flight at the same time. Requests exceeding this limit wait
+    /// until another request completes. Clones of a [Client] share the limit. [Default]s to `None`, which means no limit.
+    /// `Some(0)` is treated as `Some(1)`, and values larger than [Semaphore::MAX_PERMITS] are treated as that value.
+    pub max_concurrent_requests: Option<usize>,
+}
+
+impl ClientConfig {
+    /// Constructs a new `ClientConfig` with its fields set to [default][ClientConfig::default()].
+    #[inline(always)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
         Self {
-            client,
+            tcp_keepalive: None,
+            pool_idle_timeout: Some(Duration::from_secs(90)),
+            pool_max_idle_per_host: usize::MAX,
+            max_concurrent_requests: None,
         }
     }
 }
@@ -260,6 +357,12 @@ pub struct RequestConfig {
     ///
     /// Example usage: `"https://example.com"`
     pub url_prefix: String,
+    /// The maximum size of a response body, in bytes. [Client::request()] returns [RequestError::ResponseTooLarge]
+    /// instead of reading a larger body. [Default]s to `None`, which means no limit.
+    pub max_response_size: Option<usize>,
+    /// The User-Agent header sent with requests using this configuration.
+    /// [Default]s to `None`, which means [USER_AGENT] is sent.
+    pub user_agent: Option<String>,
 }
 
 impl RequestConfig {
@@ -282,6 +385,8 @@ impl Default for RequestConfig {
             retry_cooldown: Duration::from_millis(500),
             timeout: Duration::from_secs(3),
             url_prefix: String::new(),
+            max_response_size: None,
+            user_agent: None,
         }
     }
 }
@@ -303,4 +408,7 @@ pub enum RequestError<E, R> {
     /// An error which was returned by [RequestHandler].
     #[error("the response handler returned an error")]
     ResponseHandleError(R),
+    /// The response body was larger than [RequestConfig::max_response_size].
+    #[error("the response body exceeded the size limit")]
+    ResponseTooLarge,
 }
@@ -1,20 +1,23 @@
 use std::{
+    fmt,
     sync::{Arc, atomic::{AtomicBool, Ordering}},
-    collections::hash_map::{HashMap, Entry},
-    time::Duration,
+    collections::{VecDeque, hash_map::{HashMap, Entry}},
+    time::{Duration, Instant},
     mem,
 };
 use tokio::{
-    sync::{mpsc as tokio_mpsc, Mutex as AsyncMutex, Notify},
+    sync::{mpsc::{self as tokio_mpsc, error::TryRecvError}, oneshot, Mutex as AsyncMutex, Notify},
     task::JoinHandle,
     net::TcpStream,
-    time::{MissedTickBehavior, timeout},
+    time::{MissedTickBehavior, timeout, timeout_at},
 };
 use tokio_tungstenite::{
     tungstenite,
     MaybeTlsStream,
 };
-pub use tungstenite::Error as TungsteniteError;
+pub use tungstenite::{Error as TungsteniteError, protocol::{CloseFrame, frame::coding::CloseCode}};
+#[cfg(any(feature = "native-tls", feature = "native-tls-vendored", feature = "rustls-tls-native-roots", feature = "rustls-tls-webpki-roots"))]
+pub use tokio_tungstenite::Connector as TlsConnector;
 use futures_util::{
     sink::SinkExt,
     stream::{StreamExt, SplitSink},
@@ -46,10 +49,18 @@ pub struct WebSocketConnection<H: WebSocketHandler> {
     reconnect_state: ReconnectState,
 }
 
-// Two ways connections end:
+// Ways connections end:
 // - User drops WebSocketConnection
 //     1. feed_handler receives a message and closes the connection, then terminates
 //     2. start_connection notices that the connection is closed, and attempts to notify feed_handler, then terminates
+// - User calls WebSocketConnection::close()
+//     1. close() waits for an ongoing reconnection to complete, then aborts task_reconnect
+//     2. feed_handler receives a message, sends the messages returned by handle_shutdown() and a close frame
+//     3. start_connection notices that the server closed the connection, and notifies feed_handler, then terminates
+//     4. feed_handler receives the message, notifies close(), then terminates
+// - Reconnection fails WebSocketConfig::max_failed_reconnects times in a row, or WebSocketConfig::should_reconnect returns false
+//     1. task_reconnect notifies feed_handler, then terminates
+//     2. feed_handler receives the message and closes the connection, then terminates
 // - Reconnection
 //     This happens when:
 //     - the user requests so
@@ -64,15 +75,38 @@ pub struct WebSocketConnection<H: WebSocketHandler> {
 #[derive(Debug)]
 struct ConnectionInner<H: WebSocketHandler> {
     url: String,
+    config: WebSocketConfig,
     handler: Arc<SyncMutex<H>>,
     message_tx: tokio_mpsc::UnboundedSender<(bool, FeederMessage)>,
     next_connection_id: AtomicBool,
+    last_message_at: SyncMutex<Instant>,
+    last_ping_at: SyncMutex<Option<Instant>>,
+    message_buffer: SyncMutex<MessageBuffer>,
+    message_pushed: Notify,
+    message_popped: Notify,
+    // held by task_reconnect while it is reconnecting
+    reconnect_lock: AsyncMutex<()>,
 }
 
+// Message and ConnectionClosed are passed through ConnectionInner::message_buffer, and the others through message_tx
+#[derive(Debug)]
 enum FeederMessage {
     Message(tungstenite::Result<tungstenite::Message>),
-    ConnectionClosed,
+    // contains the close frame sent by the server, if any
+    ConnectionClosed(Option<CloseFrame<'static>>),
     DropConnectionRequest,
+    CloseRequest(oneshot::Sender<()>),
+    GiveUp,
+}
+
+// messages received from the server, waiting to be handled by feed_handler
+#[derive(Debug, Default)]
+struct MessageBuffer {
+    messages: VecDeque<(bool, FeederMessage)>,
+    // true from when the buffer becomes full until it is emptied, so that an overflow is logged only once
+    full: bool,
+    // the number of messages dropped since the buffer became full
+    dropped: usize,
 }
 
 impl<H: WebSocketHandler> WebSocketConnection<H> {
@@ -87,9 +121,16 @@ impl<H: WebSocketHandler> WebSocketConnection<H> {
 
         let connection = Arc::new(ConnectionInner {
             url,
+            config: config.clone(),
             handler: Arc::clone(&handler),
             message_tx,
             next_connection_id: AtomicBool::new(false),
+            last_message_at: SyncMutex::new(Instant::now()),
+            last_ping_at: SyncMutex::new(None),
+            message_buffer: SyncMutex::new(MessageBuffer::default()),
+            message_pushed: Notify::new(),
+            message_popped: Notify::new(),
+            reconnect_lock: AsyncMutex::new(()),
         });
 
         async fn feed_handler(
@@ -100,6 +141,9 @@ impl<H: WebSocketHandler> WebSocketConnection<H> {
             sink: Arc<AsyncMutex<WebSocketSplitSink>>,
         ) {
             let mut messages: HashMap<WebSocketMessage, isize> = HashMap::new();
+            // Some when WebSocketConnection::close() was called
+            let mut closing: Option<oneshot::Sender<()>> = None;
+            let mut close_reason = CloseReason::Dropped;
 
             let timeout_duration = if config.message_timeout.is_zero() {
                 Duration::MAX
@@ -108,7 +152,7 @@ impl<H: WebSocketHandler> WebSocketConnection<H> {
             };
 
             loop {
-                match timeout(timeout_duration, message_rx.recv()).await {
+                match timeout(timeout_duration, connection.receive(&mut message_rx)).await {
                     // message successfully received
                     Ok(Some((id, FeederMessage::Message(Ok(message))))) => {
                         // message successfully received
@@ -144,7 +188,11 @@ impl<H: WebSocketHandler> WebSocketConnection<H> {
                             } else {
                                 messages.clear();
                             }
-                            let messages = connection.handler.lock().handle_message(message);
+                            let messages = {
+                                #[cfg(feature = "tracing")]
+                                let _span = tracing::trace_span!("handle_message", url = %connection.url).entered();
+                                connection.handler.lock().handle_message(message)
+                            };
                             let mut sink_lock = sink.lock().await;
                             for message in messages {
                                 if let Err(error) = sink_lock.send(message.into_message()).await {
@@ -159,26 +207,36 @@ impl<H: WebSocketHandler> WebSocketConnection<H> {
                     // failed to receive message
                     Ok(Some((_, FeederMessage::Message(Err(error))))) => {
                         log::error!("Failed to receive message because of an error: {error:?}");
-                        if reconnect_manager.request_reconnect() {
+                        if closing.is_some() {
+                            break;
+                        }
+                        if reconnect_manager.request_reconnect_with(CloseReason::Error) {
                             log::info!("Reconnecting WebSocket because there was an error while receiving a message");
                         }
                     },
                     // timeout
                     Err(_) => {
                         log::debug!("WebSocket message timeout");
-                        if reconnect_manager.request_reconnect() {
+                        if closing.is_some() {
+                            continue;
+                        }
+                        if reconnect_manager.request_reconnect_with(CloseReason::MessageTimeout) {
                             log::info!("Reconnecting WebSocket because of timeout");
                         }
                     },
                     // connection was closed
-                    Ok(Some((id, FeederMessage::ConnectionClosed))) => {
+                    Ok(Some((id, FeederMessage::ConnectionClosed(close_frame)))) => {
                         let current_id = !connection.next_connection_id.load(Ordering::SeqCst);
                         if id != current_id {
                             // old connection, ignore
                             continue;
                         }
+                        if closing.is_some() {
+                            log::debug!("WebSocket connection closed gracefully");
+                            break;
+                        }
                         log::debug!("WebSocket connection closed by server");
-                        if reconnect_manager.request_reconnect() {
+                        if reconnect_manager.request_reconnect_with(CloseReason::ServerClosed(close_frame)) {
                             log::info!("Reconnecting WebSocket because it was disconnected by the server");
                         }
                     },
@@ -188,24 +246,56 @@ impl<H: WebSocketHandler> WebSocketConnection<H> {
                             log::debug!("Failed to close WebSocket connection: {error:?}");
                         }
                         break;
-                    }
+                    },
+                    // the user requested a graceful close
+                    Ok(Some((_, FeederMessage::CloseRequest(close_tx)))) => {
+                        let messages = connection.handler.lock().handle_shutdown();
+                        let mut sink_lock = sink.lock().await;
+                        for message in messages {
+                            if let Err(error) = sink_lock.send(message.into_message()).await {
+                                log::error!("Failed to send message because of an error: {}", error);
+                            };
+                        }
+                        // sends a close frame and waits for the server to close the connection
+                        if let Err(error) = sink_lock.close().await {
+                            log::debug!("Failed to close WebSocket connection: {error:?}");
+                            break;
+                        }
+                        closing = Some(close_tx);
+                    },
+                    // task_reconnect gave up reconnecting
+                    Ok(Some((_, FeederMessage::GiveUp))) => {
+                        if let Err(error) = sink.lock().await.close().await {
+                            log::debug!("Failed to close WebSocket connection: {error:?}");
+                        }
+                        close_reason = CloseReason::GaveUp;
+                        break;
+                    },
                     // message_tx has been dropped, which should never happen because it's always accessible by connection.message_tx.
                     Ok(None) => unreachable!("message_rx should never be closed"),
                 }
             }
-            connection.handler.lock().handle_close(false);
+            // wake up the tasks waiting for the buffer so that they notice message_rx is closed
+            drop(message_rx);
+            connection.message_popped.notify_waiters();
+
+            connection.handler.lock().handle_close(close_reason);
+            if let Some(close_tx) = closing {
+                close_tx.send(()).ok();
+            }
         }
 
         async fn reconnect<H: WebSocketHandler>(
-            interval: Duration,
-            cooldown: Duration,
             connection: Arc<ConnectionInner<H>>,
             sink: Arc<AsyncMutex<WebSocketSplitSink>>,
             reconnect_manager: ReconnectState,
-            no_duplicate: bool,
-            wait: Duration,
+            config: WebSocketConfig,
         ) {
-            let mut cooldown = tokio::time::interval(cooldown);
+            let interval = config.refresh_after;
+            let no_duplicate = config.ignore_duplicate_during_reconnection;
+            let wait = config.reconnection_wait;
+            let mut failed_reconnects = 0;
+            let mut cooldown = tokio::time::interval(config.connect_cooldown);
             cooldown.set_missed_tick_behavior(MissedTickBehavior::Delay);
             loop {
                 let timer = if interval.is_zero() {
@@ -214,12 +304,27 @@ impl<H: WebSocketHandler> WebSocketConnection<H> {
                 } else {
                     tokio::time::sleep(interval)
                 };
-                tokio::select! {
-                    _ = reconnect_manager.inner.reconnect_notify.notified() => {},
-                    _ = timer => {},
+                let reason = tokio::select! {
+                    _ = reconnect_manager.inner.reconnect_notify.notified() => reconnect_manager.inner.reason.lock().clone(),
+                    _ = timer => CloseReason::Refresh,
+                };
+                log::debug!("Reconnection requested; reason: {reason:?}");
+                if let Some(should_reconnect) = &config.should_reconnect {
+                    if !should_reconnect(&reason, failed_reconnects + 1) {
+                        if matches!(reason, CloseReason::Refresh | CloseReason::Requested) {
+                            // the current connection is still alive, so we keep using it
+                            log::debug!("Skipping reconnection because should_reconnect returned false; reason: {reason:?}");
+                            failed_reconnects = 0;
+                            continue;
+                        }
+                        log::info!("Not reconnecting WebSocket because should_reconnect returned false; reason: {reason:?}");
+                        give_up(&connection, &reconnect_manager);
+                        return;
+                    }
                 }
-                log::debug!("Reconnection requested");
                 cooldown.tick().await;
+                // prevents WebSocketConnection::close() from aborting this task halfway
+                let _reconnect_guard = connection.reconnect_lock.lock().await;
                 reconnect_manager.inner.reconnecting.store(true, Ordering::SeqCst);
 
                 // reconnect_notify might have been notified while waiting the cooldown,
@@ -247,12 +352,20 @@ impl<H: WebSocketHandler> WebSocketConnection<H> {
                         if let Err(error) = old_sink.close().await {
                             log::debug!("An error occurred while closing old connection: {}", error);
                         }
-                        connection.handler.lock().handle_close(true);
+                        connection.handler.lock().handle_close(reason);
                         log::debug!("Old connection closed");
+                        failed_reconnects = 0;
                     },
                     Err(error) => {
+                        failed_reconnects += 1;
+                        if config.max_failed_reconnects.is_some_and(|max| failed_reconnects >= max) {
+                            log::error!("Failed to reconnect {} times in a row because of an error: {}, giving up", failed_reconnects, error);
+                            give_up(&connection, &reconnect_manager);
+                            return;
+                        }
                         // try reconnecting again
                         log::error!("Failed to reconnect because of an error: {}, trying again ...", error);
+                        *reconnect_manager.inner.reason.lock() = reason;
                         reconnect_manager.inner.reconnect_notify.notify_one();
                     },
                 }
@@ -266,6 +379,12 @@ impl<H: WebSocketHandler> WebSocketConnection<H> {
             }
         }
 
+        fn give_up(connection: &ConnectionInner<impl WebSocketHandler>, reconnect_manager: &ReconnectState) {
+            reconnect_manager.inner.reconnecting.store(false, Ordering::SeqCst);
+            let current_id = !connection.next_connection_id.load(Ordering::SeqCst);
+            connection.message_tx.send((current_id, FeederMessage::GiveUp)).ok();
+        }
+
         let sink_inner = Self::start_connection(Arc::clone(&connection)).await?;
         let sink = Arc::new(AsyncMutex::new(sink_inner));
 
@@ -280,13 +399,10 @@ impl<H: WebSocketHandler> WebSocketConnection<H> {
         );
 
         let task_reconnect = tokio::spawn(reconnect(
-            config.refresh_after,
-            config.connect_cooldown,
             Arc::clone(&connection),
             Arc::clone(&sink),
             reconnect_manager.clone(),
-            config.ignore_duplicate_during_reconnection,
-            config.reconnection_wait,
+            config,
         ));
 
         Ok(Self {
@@ -298,7 +414,18 @@ impl<H: WebSocketHandler> WebSocketConnection<H> {
     }
 
     async fn start_connection(connection: Arc<ConnectionInner<impl WebSocketHandler>>) -> Result<WebSocketSplitSink, TungsteniteError> {
-        let (websocket_stream, _) = tokio_tungstenite::connect_async(connection.url.clone()).await?;
+        let tungstenite_config = tungstenite::protocol::WebSocketConfig {
+            max_message_size: connection.config.max_message_size,
+            ..Default::default()
+        };
+        #[cfg(any(feature = "native-tls", feature = "native-tls-vendored", feature = "rustls-tls-native-roots", feature = "rustls-tls-webpki-roots"))]
+        let (websocket_stream, _) = tokio_tungstenite::connect_async_tls_with_config(
+            connection.url.clone(), Some(tungstenite_config), false, connection.config.tls_connector.clone(),
+        ).await?;
+        #[cfg(not(any(feature = "native-tls", feature = "native-tls-vendored", feature = "rustls-tls-native-roots", feature = "rustls-tls-webpki-roots")))]
+        let (websocket_stream, _) = tokio_tungstenite::connect_async_with_config(
+            connection.url.clone(), Some(tungstenite_config), false,
+        ).await?;
         let (mut sink, mut stream) = websocket_stream.split();
 
         let messages = connection.handler.lock().handle_start();
@@ -312,9 +439,20 @@ impl<H: WebSocketHandler> WebSocketConnection<H> {
 
         // pass messages to task_feed_handler
         tokio::spawn(async move {
+            let mut close_frame = None;
             while let Some(message) = stream.next().await {
-                // send the received message to the task running feed_handler
-                if connection.message_tx.send((id, FeederMessage::Message(message))).is_err() {
+                if let Ok(message) = &message {
+                    // recorded here rather than in feed_handler so that messages waiting in the buffer are counted
+                    let now = Instant::now();
+                    *connection.last_message_at.lock() = now;
+                    match message {
+                        tungstenite::Message::Ping(_) => *connection.last_ping_at.lock() = Some(now),
+                        tungstenite::Message::Close(Some(frame)) => close_frame = Some(frame.clone().into_owned()),
+                        _ => (),
+                    }
+                }
+                // pass the received message to the task running feed_handler
+                if !connection.push_message(id, FeederMessage::Message(message)).await {
                     // the channel is closed. we can't disconnect because we don't have the sink
                     log::debug!("WebSocket message receiver is closed; abandon connection");
                     return;
@@ -322,7 +460,8 @@ impl<H: WebSocketHandler> WebSocketConnection<H> {
             }
             // the underlying WebSocket connection was closed
 
-            drop(connection.message_tx.send((id, FeederMessage::ConnectionClosed))); // this may be Err
+            // sent through the buffer so that it is handled after the messages received before it
+            connection.push_message(id, FeederMessage::ConnectionClosed(close_frame)).await; // this may be false
             log::debug!("WebSocket stream closed");
         });
         Ok(sink)
@@ -335,6 +474,48 @@ impl<H: WebSocketHandler> WebSocketConnection<H> {
         sink_lock.flush().await
     }
 
+    /// Gracefully closes the connection.
+    ///
+    /// The messages returned by [WebSocketHandler::handle_shutdown()] are sent, followed by a close frame.
+    /// This method then waits until the server closes the connection, or `wait` has elapsed.
+    /// If a reconnection is in progress, it is completed first. `wait` includes the time spent waiting for it.
+    /// No reconnection will be performed after this method is called.
+    pub async fn close(self, wait: Duration) {
+        let deadline = tokio::time::Instant::now() + wait;
+        {
+            // aborting task_reconnect in the middle of a reconnection would leave the new connection open
+            let Ok(_reconnect_guard) = timeout_at(deadline, self.inner.reconnect_lock.lock()).await else {
+                log::debug!("Timed out waiting for the reconnection to complete; dropping the WebSocket connection");
+                return;
+            };
+            self.task_reconnect.abort();
+        }
+        let (close_tx, close_rx) = oneshot::channel();
+        let current_id = !self.inner.next_connection_id.load(Ordering::SeqCst);
+        if self.inner.message_tx.send((current_id, FeederMessage::CloseRequest(close_tx))).is_err() {
+            // feed_handler has already terminated
+            return;
+        }
+        if timeout_at(deadline, close_rx).await.is_err() {
+            log::debug!("Timed out waiting for the server to close the WebSocket connection");
+        }
+    }
+
+    /// Returns the time elapsed since the last message was received from the server.
+    ///
+    /// Every kind of message, including ping and pong messages, is taken into account as soon as it is received,
+    /// even if it is still waiting to be handled by [WebSocketHandler::handle_message()] or is dropped because of
+    /// [WebSocketConfig::overflow_policy].
+    /// If no message has been received yet, returns the time elapsed since this `WebSocketConnection` was created.
+    pub fn time_since_last_message(&self) -> Duration {
+        self.inner.last_message_at.lock().elapsed()
+    }
+
+    /// Returns when the last ping message was received from the server, or `None` if none has been received.
+    pub fn last_ping_at(&self) -> Option<Instant> {
+        *self.inner.last_ping_at.lock()
+    }
+
     /// Returns a [ReconnectState] for this connection.
     ///
     /// See [ReconnectState] for more information.
@@ -343,6 +524,92 @@ impl<H: WebSocketHandler> WebSocketConnection<H> {
     }
 }
 
+impl<H: WebSocketHandler> ConnectionInner<H> {
+    /// Pushes a message received from the server to the buffer. Applies [WebSocketConfig::overflow_policy] if the buffer is full.
+    ///
+    /// Returns `false` if feed_handler has terminated.
+    async fn push_message(&self, id: bool, message: FeederMessage) -> bool {
+        let capacity = self.config.message_buffer_size.map(|capacity| capacity.max(1));
+        // errors and ConnectionClosed are never dropped because they trigger a reconnection
+        let droppable = matches!(message, FeederMessage::Message(Ok(_)));
+        loop {
+            // created before checking the buffer so that we don't miss notifications
+            let popped = self.message_popped.notified();
+            if self.message_tx.is_closed() {
+                return false;
+            }
+            {
+                let mut buffer = self.message_buffer.lock();
+                if !droppable || capacity.is_none_or(|capacity| buffer.messages.len() < capacity) {
+                    buffer.messages.push_back((id, message));
+                    self.message_pushed.notify_one();
+                    return true;
+                }
+                if !mem::replace(&mut buffer.full, true) {
+                    if self.config.overflow_policy == OverflowPolicy::Block {
+                        // no message is lost, so this is not worth a warning
+                        log::debug!("WebSocket message buffer is full; waiting for the handler");
+                    } else {
+                        log::warn!("WebSocket message buffer is full; overflow policy: {:?}", self.config.overflow_policy);
+                    }
+                }
+                match self.config.overflow_policy {
+                    OverflowPolicy::DropOldest => {
+                        let oldest = buffer.messages.iter().position(|(_, message)| matches!(message, FeederMessage::Message(Ok(_))));
+                        if let Some(index) = oldest {
+                            buffer.messages.remove(index);
+                            buffer.dropped += 1;
+                        }
+                        buffer.messages.push_back((id, message));
+                        self.message_pushed.notify_one();
+                        return true;
+                    },
+                    OverflowPolicy::DropNewest => {
+                        buffer.dropped += 1;
+                        return true;
+                    },
+                    OverflowPolicy::Block => (),
+                }
+            }
+            popped.await;
+        }
+    }
+
+    /// Receives the next message for feed_handler.
+    ///
+    /// Messages from message_tx are returned before the buffered messages, so that a busy connection can still be
+    /// dropped or closed. [FeederMessage::ConnectionClosed] goes through the buffer, so it is returned after the
+    /// messages received before it.
+    async fn receive(&self, message_rx: &mut tokio_mpsc::UnboundedReceiver<(bool, FeederMessage)>) -> Option<(bool, FeederMessage)> {
+        loop {
+            match message_rx.try_recv() {
+                Ok(received) => return Some(received),
+                Err(TryRecvError::Disconnected) => return None,
+                Err(TryRecvError::Empty) => (),
+            }
+            let popped = {
+                let mut buffer = self.message_buffer.lock();
+                let popped = buffer.messages.pop_front();
+                if popped.is_some() && buffer.messages.is_empty() && mem::take(&mut buffer.full) {
+                    match mem::take(&mut buffer.dropped) {
+                        0 => log::debug!("WebSocket message buffer has been emptied"),
+                        dropped => log::warn!("WebSocket message buffer has been emptied; {dropped} messages were dropped"),
+                    }
+                }
+                popped
+            };
+            if let Some(received) = popped {
+                self.message_popped.notify_waiters();
+                return Some(received);
+            }
+            tokio::select! {
+                _ = self.message_pushed.notified() => (),
+                received = message_rx.recv() => return received,
+            }
+        }
+    }
+}
+
 impl<H: WebSocketHandler> Drop for WebSocketConnection<H> {
     fn drop(&mut self) {
         self.task_reconnect.abort();
@@ -365,6 +632,7 @@ pub struct ReconnectState {
 struct ReconnectMangerInner {
     reconnect_notify: Notify,
     reconnecting: AtomicBool,
+    reason: SyncMutex<CloseReason>,
 }
 
 impl ReconnectState {
@@ -373,6 +641,7 @@ impl ReconnectState {
             inner: Arc::new(ReconnectMangerInner {
                 reconnect_notify: Notify::new(),
                 reconnecting: AtomicBool::new(false),
+                reason: SyncMutex::new(CloseReason::Requested),
             })
         }
     }
@@ -385,16 +654,71 @@ impl ReconnectState {
     /// Request the [WebSocketConnection] to perform a reconnect.
     ///
     /// Will return `false` if it is already in a reconnection process.
+    /// [WebSocketHandler::handle_close()] will be called with [CloseReason::Requested].
     pub fn request_reconnect(&self) -> bool {
+        self.request_reconnect_with(CloseReason::Requested)
+    }
+
+    fn request_reconnect_with(&self, reason: CloseReason) -> bool {
         if self.is_reconnecting() {
             false
         } else {
+            *self.inner.reason.lock() = reason;
             self.inner.reconnect_notify.notify_one();
             true
         }
     }
 }
 
+/// An `enum` that represents why a websocket connection was closed.
+///
+/// This is passed to [WebSocketHandler::handle_close()].
+#[derive(Debug, Eq, PartialEq, Clone)]
+#[non_exhaustive]
+pub enum CloseReason {
+    /// The [WebSocketConnection] was dropped or [closed][WebSocketConnection::close()].
+    /// The connection will not be reconnected.
+    Dropped,
+    /// The server closed the connection. Contains the close frame sent by the server, if any.
+    ///
+    /// The close frame tells why the server closed the connection, for example [CloseCode::Policy]
+    /// when authentication was rejected.
+    ServerClosed(Option<CloseFrame<'static>>),
+    /// No message was received within [WebSocketConfig::message_timeout].
+    MessageTimeout,
+    /// An error occurred while receiving a message.
+    Error,
+    /// [WebSocketConfig::refresh_after] has elapsed since the last connection started.
+    Refresh,
+    /// A reconnection was requested using [ReconnectState::request_reconnect()].
+    Requested,
+    /// Reconnection failed [WebSocketConfig::max_failed_reconnects] times in a row,
+    /// or [WebSocketConfig::should_reconnect] returned `false` after the connection failed. The connection will not be reconnected.
+    GaveUp,
+}
+
+impl CloseReason {
+    /// Returns `true` iff the connection is being reconnected.
+    pub fn is_reconnect(&self) -> bool {
+        !matches!(self, Self::Dropped | Self::GaveUp)
+    }
+}
+
+/// An `enum` that represents what [WebSocketConnection] does when the message buffer is full.
+///
+/// See [WebSocketConfig::message_buffer_size].
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[non_exhaustive]
+pub enum OverflowPolicy {
+    /// The oldest message in the buffer is dropped to make room for the new one.
+    DropOldest,
+    /// The new message is dropped.
+    DropNewest,
+    /// Stop reading from the connection until the [WebSocketHandler] catches up. No message is dropped,
+    /// but the server may close the connection if it is blocked for too long.
+    Block,
+}
+
 /// An enum that represents a websocket message.
 ///
 /// See also [tungstenite::Message].
@@ -452,21 +776,30 @@ pub trait WebSocketHandler: Send + 'static {
     /// Called when the [WebSocketConnection] received a message, returns messages to be sent to the server.
     fn handle_message(&mut self, message: WebSocketMessage) -> Vec<WebSocketMessage>;
 
+    /// Called when [WebSocketConnection::close()] is called, and returns messages that should be sent to the server
+    /// before the connection is closed.
+    fn handle_shutdown(&mut self) -> Vec<WebSocketMessage> {
+        vec![]
+    }
+
     /// Called when a websocket connection is closed.
     ///
-    /// If the parameter `reconnect` is:
-    /// - `true`, it means that the connection is being reconnected for some reason.
-    /// - `false`, it means that the connection will not be reconnected, because the [WebSocketConnection] was dropped.
+    /// The parameter `reason` tells why the connection was closed.
+    /// The connection is being reconnected unless `reason` is [CloseReason::Dropped] or [CloseReason::GaveUp].
+    /// See also [CloseReason::is_reconnect()].
     #[allow(unused_variables)]
-    fn handle_close(&mut self, reconnect: bool) {
-        log::debug!("WebSocket connection closed; reconnect: {}", reconnect);
+    fn handle_close(&mut self, reason: CloseReason) {
+        log::debug!("WebSocket connection closed; reason: {:?}", reason);
     }
 }
 
+/// The type of [WebSocketConfig::should_reconnect].
+pub type ReconnectPredicate = Arc<dyn Fn(&CloseReason, u32) -> bool + Send + Sync>;
+
 /// Configuration for [WebSocketHandler].
 ///
 /// Should be returned by [WebSocketHandler::websocket_config()].
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 #[non_exhaustive]
 pub struct WebSocketConfig {
     /// Duration that should elapse between each attempt to start a new connection.
@@ -495,6 +828,42 @@ pub struct WebSocketConfig {
     /// A reconnection will be triggered if no messages are received within this amount of time.
     /// [Default]s to [Duration::ZERO], which means no timeout will be applied.
     pub message_timeout: Duration,
+    /// [WebSocketConnection] stops reconnecting when it failed to reconnect this many times in a row.
+    /// When this happens, [WebSocketHandler::handle_close()] is called with [CloseReason::GaveUp].
+    /// `Some(0)` is treated as `Some(1)`, which means it gives up after the first failure.
+    /// [Default]s to `None`, which means it keeps trying forever.
+    pub max_failed_reconnects: Option<u32>,
+    /// A function that decides whether [WebSocketConnection] should reconnect. It is called with the reason of
+    /// the reconnection and the number of the attempt, which starts at 1 and increases while reconnection keeps failing.
+    ///
+    /// What happens when it returns `false` depends on the reason:
+    /// - For [CloseReason::Refresh] and [CloseReason::Requested], the reconnection is skipped and the current
+    ///   connection keeps being used.
+    /// - For other reasons, the connection has failed, so [WebSocketConnection] stops reconnecting and
+    ///   [WebSocketHandler::handle_close()] is called with [CloseReason::GaveUp].
+    ///
+    /// [Default]s to `None`, which means it always reconnects.
+    pub should_reconnect: Option<ReconnectPredicate>,
+    /// The maximum size of a message received from the server, in bytes. A larger message is treated as an error,
+    /// which causes a reconnection. `None` means no limit. [Default]s to 64 MiB.
+    pub max_message_size: Option<usize>,
+    /// The maximum number of received messages that are waiting to be handled by [WebSocketHandler::handle_message()].
+    /// When the buffer is full, [overflow_policy][Self::overflow_policy] is applied. If messages are dropped, a warning is
+    /// logged when the buffer becomes full, and another one with the number of dropped messages when the handler has emptied it.
+    /// Errors are never dropped because they trigger a reconnection. `Some(0)` is treated as `Some(1)`.
+    /// [Default]s to `None`, which means the buffer is unbounded.
+    pub message_buffer_size: Option<usize>,
+    /// What to do when the buffer set by [message_buffer_size][Self::message_buffer_size] is full.
+    /// [Default]s to [OverflowPolicy::Block].
+    pub overflow_policy: OverflowPolicy,
+    /// The [TlsConnector] used to establish `wss://` connections. [Default]s to `None`,
+    /// which means a connector is created using the TLS library selected by the cargo features.
+    ///
+    /// Use this to trust custom root certificates or to pin certificates, for example when connecting through
+    /// a TLS-terminating proxy. This field is only available when one of the cargo features `native-tls`,
+    /// `native-tls-vendored`, `rustls-tls-native-roots` or `rustls-tls-webpki-roots` is enabled.
+    #[cfg(any(feature = "native-tls", feature = "native-tls-vendored", feature = "rustls-tls-native-roots", feature = "rustls-tls-webpki-roots"))]
+    pub tls_connector: Option<TlsConnector>,
 }
 
 impl WebSocketConfig {
@@ -513,6 +882,35 @@ impl Default for WebSocketConfig {
             ignore_duplicate_during_reconnection: false,
             reconnection_wait: Duration::from_millis(300),
             message_timeout: Duration::ZERO,
+            max_failed_reconnects: None,
+            should_reconnect: None,
+            max_message_size: Some(64 << 20),
+            message_buffer_size: None,
+            overflow_policy: OverflowPolicy::Block,
+            #[cfg(any(feature = "native-tls", feature = "native-tls-vendored", feature = "rustls-tls-native-roots", feature = "rustls-tls-webpki-roots"))]
+            tls_connector: None,
         }
     }
 }
+
+// TlsConnector and closures don't implement Debug
+impl fmt::Debug for WebSocketConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("WebSocketConfig");
+        debug
+            .field("connect_cooldown", &self.connect_cooldown)
+            .field("refresh_after", &self.refresh_after)
+            .field("url_prefix", &self.url_prefix)
+            .field("ignore_duplicate_during_reconnection", &self.ignore_duplicate_during_reconnection)
+            .field("reconnection_wait", &self.reconnection_wait)
+            .field("message_timeout", &self.message_timeout)
+            .field("max_failed_reconnects", &self.max_failed_reconnects)
+            .field("should_reconnect", &self.should_reconnect.as_ref().map(|_| ".."))
+            .field("max_message_size", &self.max_message_size)
+            .field("message_buffer_size", &self.message_buffer_size)
+            .field("overflow_policy", &self.overflow_policy);
+        #[cfg(any(feature = "native-tls", feature = "native-tls-vendored", feature = "rustls-tls-native-roots", feature = "rustls-tls-webpki-roots"))]
+        debug.field("tls_connector", &self.tls_connector.as_ref().map(|_| ".."));
+        debug.finish()
+    }
+}
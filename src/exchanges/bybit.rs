@@ -14,8 +14,10 @@ pub type BybitRequestResult<T> = Result<T, BybitRequestError>;
 pub type BybitRequestError = RequestError<&'static str, BybitHandlerError>;
 
 /// Options that can be set when creating handlers
+#[derive(Default)]
 pub enum BybitOption {
     /// [Default] variant, does nothing
+    #[default]
     Default,
     /// API key
     Key(String),
@@ -524,9 +526,3 @@ impl <H: FnMut(serde_json::Value) + Send + 'static> WebSocketOption<H> for Bybit
 impl HandlerOption for BybitOption {
     type Options = BybitOptions;
 }
-
-impl Default for BybitOption {
-    fn default() -> Self {
-        Self::Default
-    }
-}
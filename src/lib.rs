@@ -42,6 +42,22 @@ impl Client {
         Self::default()
     }
 
+    /// Creates a new [Client] whose connections are configured by the given [ClientConfig].
+    pub fn with_config(config: ClientConfig) -> Self {
+        // not using `..Default::default()` because it would build an extra http::Client
+        Self {
+            client: http::Client::with_config(config),
+            #[cfg(feature = "binance")]
+            binance: Default::default(),
+            #[cfg(feature = "bitflyer")]
+            bitflyer: Default::default(),
+            #[cfg(feature = "bybit")]
+            bybit: Default::default(),
+            #[cfg(feature = "coincheck")]
+            coincheck: Default::default(),
+        }
+    }
+
     /// Update the default options for this [Client]
     #[inline(always)]
     pub fn update_default_option<O>(&mut self, option: O)